@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use libloading::{Library, Symbol};
+use tree_sitter::Language;
+
+/// Derives the grammar name `--grammar` should resolve, e.g.
+/// `libtree-sitter-python.so` or `tree-sitter-python.dll` both become
+/// `python`.
+pub fn infer_grammar_name(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+    let stem = stem.strip_prefix("lib").unwrap_or(stem);
+    stem.strip_prefix("tree-sitter-").unwrap_or(stem).to_owned()
+}
+
+/// Loads a compiled `tree-sitter-<name>` shared object and resolves its
+/// `tree_sitter_<name>()` entry point to obtain a `Language`, the same way
+/// `--filter` plugins are `dlopen`ed and have their entry point resolved.
+///
+/// The loaded library is intentionally leaked: the returned `Language`
+/// borrows code from it, so it must outlive the process rather than be
+/// dropped when this function returns.
+pub fn load_grammar(path: &Path, name: &str) -> Language {
+    let library = unsafe { Library::new(path) }
+        .unwrap_or_else(|err| panic!("couldn't load grammar {}: {err}", path.display()));
+    let language = unsafe {
+        let constructor: Symbol<unsafe extern "C" fn() -> Language> = library
+            .get(format!("tree_sitter_{name}\0").as_bytes())
+            .unwrap_or_else(|err| {
+                panic!(
+                    "couldn't find tree_sitter_{name}() in {}: {err}",
+                    path.display()
+                )
+            });
+        constructor()
+    };
+    std::mem::forget(library);
+    language
+}
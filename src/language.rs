@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use tree_sitter::Language;
+
+use crate::grammar::{infer_grammar_name, load_grammar};
+
+/// The set of languages `--language`/auto-detection can resolve to: the
+/// three built-ins, plus whatever `--grammar`/`--grammar-config` load at
+/// startup.
+pub struct LanguageRegistry {
+    languages_by_name: HashMap<String, Language>,
+    names_by_extension: HashMap<String, String>,
+}
+
+impl LanguageRegistry {
+    pub fn with_builtins() -> Self {
+        let mut registry = Self {
+            languages_by_name: HashMap::new(),
+            names_by_extension: HashMap::new(),
+        };
+        registry.register("rust", tree_sitter_rust::language(), &["rs"]);
+        registry.register(
+            "typescript",
+            tree_sitter_typescript::language_typescript(),
+            &["ts", "tsx"],
+        );
+        registry.register("javascript", tree_sitter_javascript::language(), &["js"]);
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, language: Language, extensions: &[&str]) {
+        for extension in extensions {
+            self.names_by_extension
+                .insert((*extension).to_owned(), name.to_owned());
+        }
+        self.languages_by_name.insert(name.to_owned(), language);
+    }
+
+    /// Loads a `--grammar <extension>=<path>` shared object and registers it
+    /// under its inferred name for both `--language <name>` and
+    /// auto-detection of files with `<extension>`, the same
+    /// `<extension>=<path>` shape `--grammar-config` lines use.
+    pub fn register_grammar_spec(&mut self, spec: &str) {
+        let (extension, path) = parse_grammar_spec(spec);
+        let name = infer_grammar_name(&path);
+        let language = load_grammar(&path, &name);
+        self.register(&name, language, &[extension]);
+    }
+
+    /// Loads a config file mapping file extensions to grammar libraries, one
+    /// `<extension>=<path to shared object>` pair per line (blank lines and
+    /// lines starting with `#` are ignored), so those grammars also
+    /// participate in auto-detection by extension.
+    pub fn load_grammar_config(&mut self, path: &Path) {
+        let config_source = fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("couldn't read grammar config {}: {err}", path.display()));
+        for line in config_source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.register_grammar_spec(line);
+        }
+    }
+
+    pub fn language_named(&self, name: &str) -> Option<Language> {
+        self.languages_by_name.get(name).copied()
+    }
+
+    pub fn extensions_for(&self, name: &str) -> Vec<String> {
+        self.names_by_extension
+            .iter()
+            .filter(|(_, language_name)| *language_name == name)
+            .map(|(extension, _)| extension.clone())
+            .collect()
+    }
+
+    /// All language names this registry can currently resolve, used to seed
+    /// `--language`'s set of clap-accepted values.
+    pub fn known_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.languages_by_name.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// Splits a `<extension>=<path>` grammar spec, as used by both `--grammar`
+/// and `--grammar-config` lines.
+fn parse_grammar_spec(spec: &str) -> (&str, std::path::PathBuf) {
+    let (extension, path) = spec
+        .split_once('=')
+        .unwrap_or_else(|| panic!("invalid grammar spec {spec:?}, expected <extension>=<path>"));
+    (extension.trim(), Path::new(path.trim()).to_owned())
+}
@@ -1,49 +1,244 @@
-use clap::Parser;
-use ignore::{types::TypesBuilder, DirEntry, WalkBuilder};
+use clap::builder::PossibleValuesParser;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser};
+use ignore::{DirEntry, WalkBuilder};
 use rayon::prelude::*;
 use std::fs;
 use std::path::PathBuf;
 
+mod grammar;
+mod language;
 mod macros;
+mod replace;
 mod treesitter;
 
-use treesitter::{get_query, get_results};
+use language::LanguageRegistry;
+use replace::{replace_in_file, ReplaceOptions};
+use treesitter::{
+    enclosing_scope, get_matches_in_tree, get_query, get_results, match_range, try_get_query,
+};
 
 #[derive(Parser)]
+#[command(group(ArgGroup::new("replace_mode").args(["dry_run", "in_place"])))]
 pub struct Args {
     pub path_to_query_file: PathBuf,
+
+    /// Rewrite each match in place, substituting `$name`/`${name}` with the
+    /// source text of the query's captures (`$$` for a literal `$`).
+    ///
+    /// Requires exactly one of `--dry-run`/`--in-place`, to say whether the
+    /// rewrite should only be shown or actually applied.
+    #[arg(long, requires = "replace_mode")]
+    pub replace: Option<String>,
+
+    /// With `--replace`, print a unified diff of the rewrite instead of
+    /// applying it.
+    #[arg(long, requires = "replace")]
+    pub dry_run: bool,
+
+    /// With `--replace`, write the rewritten buffer back to each file.
+    #[arg(long, requires = "replace")]
+    pub in_place: bool,
+
+    /// Annotate each match with the chain of named definitions it's nested
+    /// inside, e.g. `[mod tests > fn it_works]`.
+    #[arg(long)]
+    pub show_scope: bool,
+
+    /// Which language to parse files as, or the name of a grammar loaded via
+    /// `--grammar`/`--grammar-config`. When omitted, files of every known
+    /// language are searched, each picked out by its file extensions.
+    #[arg(
+        short,
+        long,
+        value_parser = PossibleValuesParser::new(["rust", "typescript", "javascript"])
+    )]
+    pub language: Option<String>,
+
+    /// Load an additional tree-sitter grammar from a compiled shared object,
+    /// given as `<extension>=<path>` (e.g.
+    /// `py=./libtree-sitter-python.so`), making it available via both
+    /// `--language <name>` (inferred from the path) and auto-detection of
+    /// files with `<extension>`. Can be passed more than once.
+    #[arg(long = "grammar")]
+    pub grammars: Vec<String>,
+
+    /// A file mapping file extensions to dynamic grammar libraries, one
+    /// `<extension>=<path>` pair per line, so those grammars also
+    /// participate in auto-detection by extension.
+    #[arg(long)]
+    pub grammar_config: Option<PathBuf>,
+}
+
+/// Parses `Args` the same way `Args::parse()` would, except `--language`'s
+/// set of clap-accepted values is extended with the names of any grammars
+/// `--grammar`/`--grammar-config` load in this invocation, so clap's own
+/// validation (and its "did you mean" suggestions) covers those names too.
+pub fn parse_args() -> Args {
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let mut languages = LanguageRegistry::with_builtins();
+    for grammar_spec in scan_flag_values(&raw_args, "--grammar") {
+        languages.register_grammar_spec(&grammar_spec);
+    }
+    if let Some(grammar_config_path) = scan_flag_values(&raw_args, "--grammar-config").pop() {
+        languages.load_grammar_config(PathBuf::from(grammar_config_path).as_path());
+    }
+
+    // clap's PossibleValuesParser needs `&'static str`s; these names are only
+    // known at runtime, so (like the grammar libraries loaded above) they're
+    // leaked for the process's lifetime rather than threaded through as
+    // borrows.
+    let known_names: Vec<&'static str> = languages
+        .known_names()
+        .into_iter()
+        .map(|name| &*Box::leak(name.into_boxed_str()))
+        .collect();
+    let command = Args::command()
+        .mut_arg("language", |arg| arg.value_parser(PossibleValuesParser::new(known_names)));
+    let matches = command.get_matches();
+    Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+}
+
+/// A lightweight pre-scan for `--flag value`/`--flag=value` occurrences,
+/// used only to discover `--grammar`/`--grammar-config` ahead of the real
+/// `clap` parse so their grammar names can be validated by it too.
+fn scan_flag_values(args: &[String], flag: &str) -> Vec<String> {
+    let prefix = format!("{flag}=");
+    args.iter()
+        .enumerate()
+        .filter_map(|(i, arg)| {
+            if let Some(value) = arg.strip_prefix(&prefix) {
+                Some(value.to_owned())
+            } else if arg == flag {
+                args.get(i + 1).cloned()
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
 pub fn run(args: Args) {
+    let mut languages = LanguageRegistry::with_builtins();
+    for grammar_spec in &args.grammars {
+        languages.register_grammar_spec(grammar_spec);
+    }
+    if let Some(grammar_config_path) = &args.grammar_config {
+        languages.load_grammar_config(grammar_config_path);
+    }
+
     let query_source = fs::read_to_string(&args.path_to_query_file).unwrap();
-    let query = get_query(&query_source);
-    enumerate_project_files()
-        .par_iter()
-        .flat_map(|project_file_dir_entry| get_results(&query, project_file_dir_entry.path(), 0))
-        .for_each(|result| {
-            println!("{}", result.format());
-        });
+
+    for (language, extensions) in language_targets(&languages, args.language.as_deref()) {
+        // When auto-detecting across every known language, a query written
+        // for one grammar (e.g. `(arrow_function)`) simply doesn't compile
+        // against the others (e.g. Rust); skip those rather than treating
+        // it as an error. With an explicit `--language`, fall through to
+        // `get_query`'s panic so a genuinely bad query is still reported.
+        let query = match try_get_query(&query_source, language) {
+            Some(query) => query,
+            None if args.language.is_none() => continue,
+            None => get_query(&query_source, language),
+        };
+
+        if let Some(template) = &args.replace {
+            let options = ReplaceOptions {
+                template,
+                dry_run: args.dry_run,
+                in_place: args.in_place,
+            };
+            enumerate_project_files(&extensions)
+                .par_iter()
+                .for_each(|project_file_dir_entry| {
+                    replace_in_file(project_file_dir_entry.path(), &query, language, &options);
+                });
+            continue;
+        }
+
+        if args.show_scope {
+            enumerate_project_files(&extensions)
+                .par_iter()
+                .for_each(|project_file_dir_entry| {
+                    print_matches_with_scope(project_file_dir_entry.path(), &query, language);
+                });
+            continue;
+        }
+
+        enumerate_project_files(&extensions)
+            .par_iter()
+            .flat_map(|project_file_dir_entry| get_results(&query, project_file_dir_entry.path(), 0))
+            .for_each(|result| {
+                println!("{}", result.format());
+            });
+    }
 }
 
-fn enumerate_project_files() -> Vec<DirEntry> {
+/// Resolves `--language` to the single `(Language, extensions)` pair it
+/// names, or, when omitted, every language the registry currently knows
+/// (built-ins plus anything loaded via `--grammar`/`--grammar-config`), so
+/// auto-detection considers files in all of them rather than just `rust`.
+fn language_targets(
+    languages: &LanguageRegistry,
+    explicit_language_name: Option<&str>,
+) -> Vec<(tree_sitter::Language, Vec<String>)> {
+    let names = match explicit_language_name {
+        Some(name) => vec![name.to_owned()],
+        None => languages.known_names(),
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let language = languages
+                .language_named(&name)
+                .unwrap_or_else(|| panic!("unknown language '{name}'"));
+            (language, languages.extensions_for(&name))
+        })
+        .collect()
+}
+
+fn print_matches_with_scope(path: &std::path::Path, query: &tree_sitter::Query, language: tree_sitter::Language) {
+    let file_text_as_bytes = fs::read(path).unwrap();
+    let file_text = std::str::from_utf8(&file_text_as_bytes).expect("Expected file text to be valid UTF-8");
+    let lines: Vec<&str> = file_text.lines().collect();
+    let tree = treesitter::parse(&file_text_as_bytes, language);
+    let display_path = path.strip_prefix(".").unwrap_or(path);
+
+    for captures in get_matches_in_tree(query, &tree, &file_text_as_bytes) {
+        let (start_byte, end_byte) = match_range(&captures);
+        let scope = enclosing_scope(&tree, &file_text_as_bytes, language, start_byte, end_byte);
+        let scope_prefix = if scope.is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", scope.join(" > "))
+        };
+        let start_line = file_text[..start_byte].matches('\n').count();
+        let end_line = file_text[..end_byte.max(start_byte + 1).min(file_text.len())]
+            .matches('\n')
+            .count();
+        for line_index in start_line..=end_line {
+            if let Some(line) = lines.get(line_index) {
+                println!(
+                    "{}:{}: {}{}",
+                    display_path.display(),
+                    line_index + 1,
+                    scope_prefix,
+                    line.trim_start()
+                );
+            }
+        }
+    }
+}
+
+fn enumerate_project_files(extensions: &[String]) -> Vec<DirEntry> {
     WalkBuilder::new(".")
-        .types(
-            TypesBuilder::new()
-                .add_defaults()
-                .select("rust")
-                .build()
-                .unwrap(),
-        )
         .build()
         .into_iter()
         .filter_map(|entry| entry.ok())
         .filter(|entry| {
-            let extension = entry.path().extension();
-            if extension.is_none() {
-                return false;
-            }
-            let extension = extension.unwrap();
-            "rs" == extension
+            entry
+                .path()
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| extensions.iter().any(|known| known == extension))
         })
         .collect()
 }
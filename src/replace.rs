@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use grep::matcher::Match;
+use similar::TextDiff;
+use tree_sitter::{Language, Query};
+
+use crate::treesitter::{get_matches, match_range};
+
+pub struct ReplaceOptions<'a> {
+    pub template: &'a str,
+    pub dry_run: bool,
+    pub in_place: bool,
+}
+
+/// Runs `--replace` against a single file: locates every top-level match,
+/// renders `template` against each match's captures, and splices the
+/// replacements into the file buffer from the highest start byte to the
+/// lowest so that earlier offsets stay valid.
+pub fn replace_in_file(path: &Path, query: &Query, language: Language, options: &ReplaceOptions) {
+    let original_text = fs::read(path).unwrap();
+    let matches = get_matches(query, &original_text, language);
+
+    let mut edits: Vec<(usize, usize, String)> = matches
+        .iter()
+        .map(|captures| {
+            let (start_byte, end_byte) = match_range(captures);
+            let replacement = render_template(options.template, captures, &original_text);
+            (start_byte, end_byte, replacement)
+        })
+        .collect();
+    edits.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+    let mut non_overlapping: Vec<(usize, usize, String)> = Vec::new();
+    let mut last_end = 0;
+    for edit in edits.drain(..) {
+        if edit.0 >= last_end {
+            last_end = edit.1;
+            non_overlapping.push(edit);
+        }
+    }
+
+    let mut new_text = original_text.clone();
+    for (start_byte, end_byte, replacement) in non_overlapping.into_iter().rev() {
+        new_text.splice(start_byte..end_byte, replacement.into_bytes());
+    }
+
+    if new_text == original_text {
+        return;
+    }
+
+    if options.dry_run {
+        print_diff(path, &original_text, &new_text);
+    } else if options.in_place {
+        fs::write(path, &new_text).unwrap();
+    }
+}
+
+/// Substitutes `$name`/`${name}` placeholders in `template` with the source
+/// text of the correspondingly-named capture, and `$$` with a literal `$`.
+fn render_template(template: &str, captures: &HashMap<String, Match>, file_text: &[u8]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some((_, '$')) => {
+                chars.next();
+                output.push('$');
+            }
+            Some((_, '{')) => {
+                chars.next();
+                let name_start = i + 2;
+                let mut name_end = name_start;
+                for (j, c) in chars.by_ref() {
+                    if c == '}' {
+                        name_end = j;
+                        break;
+                    }
+                }
+                output.push_str(&capture_text(&template[name_start..name_end], captures, file_text));
+            }
+            Some((_, c)) if is_placeholder_start(c) => {
+                let name_start = i + 1;
+                let mut name_end = template.len();
+                while let Some(&(j, c)) = chars.peek() {
+                    if is_placeholder_continue(c) {
+                        chars.next();
+                    } else {
+                        name_end = j;
+                        break;
+                    }
+                }
+                output.push_str(&capture_text(&template[name_start..name_end], captures, file_text));
+            }
+            _ => output.push('$'),
+        }
+    }
+    output
+}
+
+fn is_placeholder_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_placeholder_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn capture_text(name: &str, captures: &HashMap<String, Match>, file_text: &[u8]) -> String {
+    let range = captures
+        .get(name)
+        .unwrap_or_else(|| panic!("template references unknown capture '${name}'"));
+    std::str::from_utf8(&file_text[range.start()..range.end()])
+        .unwrap()
+        .to_owned()
+}
+
+fn print_diff(path: &Path, original_text: &[u8], new_text: &[u8]) {
+    let original_text = String::from_utf8_lossy(original_text);
+    let new_text = String::from_utf8_lossy(new_text);
+    let path = path.strip_prefix(".").unwrap_or(path).display();
+    let diff = TextDiff::from_lines(original_text.as_ref(), new_text.as_ref());
+    print!(
+        "{}",
+        diff.unified_diff()
+            .header(&format!("{path} (original)"), &format!("{path} (replaced)"))
+    );
+}
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use grep::matcher::Match;
-use tree_sitter::{Language, Parser, Query, QueryCursor};
+use tree_sitter::{Language, Parser, Query, QueryCursor, Tree};
 
 pub fn get_parser(language: Language) -> Parser {
     let mut parser = Parser::new();
@@ -13,27 +15,158 @@ pub fn get_query(source: &str, language: Language) -> Query {
     Query::new(language, source).unwrap()
 }
 
+/// Like `get_query`, but returns `None` rather than panicking when `source`
+/// doesn't compile against `language`, so callers auto-detecting across
+/// every known language can skip the ones a query simply doesn't apply to
+/// (e.g. an `(arrow_function)` query against the Rust grammar).
+pub fn try_get_query(source: &str, language: Language) -> Option<Query> {
+    Query::new(language, source).ok()
+}
+
+/// Parses `file_text_as_bytes` as `language`, so callers that need more than
+/// one thing out of the resulting tree (e.g. both its matches and their
+/// enclosing scope) can do so from a single parse.
+pub fn parse(file_text_as_bytes: &[u8], language: Language) -> Tree {
+    let file_text =
+        std::str::from_utf8(file_text_as_bytes).expect("Expected file text to be valid UTF-8");
+    get_parser(language).parse(file_text, None).unwrap()
+}
+
+/// For each match of `query` against `file_text_as_bytes`, returns a map from
+/// capture name to the byte range captured under that name, so that callers
+/// (e.g. `--replace`) can interpolate capture text into a template without
+/// re-running the query per capture.
 pub fn get_matches(
     query: &Query,
-    capture_index: u32,
     file_text_as_bytes: &[u8],
     language: Language,
-) -> Vec<Match> {
+) -> Vec<HashMap<String, Match>> {
+    let tree = parse(file_text_as_bytes, language);
+    get_matches_in_tree(query, &tree, file_text_as_bytes)
+}
+
+/// Like `get_matches`, but against a tree the caller already parsed.
+pub fn get_matches_in_tree(
+    query: &Query,
+    tree: &Tree,
+    file_text_as_bytes: &[u8],
+) -> Vec<HashMap<String, Match>> {
     let mut query_cursor = QueryCursor::new();
-    let file_text =
-        std::str::from_utf8(file_text_as_bytes).expect("Expected file text to be valid UTF-8");
-    let tree = get_parser(language).parse(file_text, None).unwrap();
+    let capture_names = query.capture_names();
     query_cursor
         .matches(query, tree.root_node(), file_text_as_bytes)
-        .flat_map(|match_| {
+        .map(|match_| {
             match_
-                .nodes_for_capture_index(capture_index)
-                .collect::<Vec<_>>()
+                .captures
+                .iter()
+                .map(|capture| {
+                    let range = capture.node.range();
+                    (
+                        capture_names[capture.index as usize].clone(),
+                        Match::new(range.start_byte, range.end_byte),
+                    )
+                })
+                .collect()
         })
-        .map(|node| {
-            let range = node.range();
+        .collect()
+}
 
-            Match::new(range.start_byte, range.end_byte)
+/// The byte range a match occupies as a whole: the union of all of its
+/// captures', so that consumers that care about "the matched node" rather
+/// than an individual capture (`--replace`, `--show-scope`) have a single
+/// range to work with.
+pub fn match_range(captures: &HashMap<String, Match>) -> (usize, usize) {
+    captures
+        .values()
+        .fold(None, |acc: Option<(usize, usize)>, m| match acc {
+            None => Some((m.start(), m.end())),
+            Some((start, end)) => Some((start.min(m.start()), end.max(m.end()))),
         })
-        .collect()
+        .expect("match had no captures")
+}
+
+/// Node kinds that `--show-scope` treats as naming a definition. Only Rust
+/// has any; for every other language the list is empty, so no scope is
+/// reported.
+fn definition_node_kinds(language: Language) -> &'static [&'static str] {
+    if language == tree_sitter_rust::language() {
+        &[
+            "function_item",
+            "impl_item",
+            "mod_item",
+            "struct_item",
+            "enum_item",
+            "trait_item",
+        ]
+    } else {
+        &[]
+    }
+}
+
+/// A human-readable label for a definition node kind, e.g. `function_item`
+/// becomes `fn` to match how the definition reads in source.
+fn scope_label(node_kind: &str) -> &str {
+    match node_kind {
+        "function_item" => "fn",
+        "mod_item" => "mod",
+        "struct_item" => "struct",
+        "enum_item" => "enum",
+        "trait_item" => "trait",
+        _ => node_kind,
+    }
+}
+
+/// The scope-chain label for a single definition node, e.g. `fn it_works` or,
+/// for `impl_item` (which, unlike the other definition kinds, has no `name`
+/// field), `impl Foo` / `impl Trait for Foo` built from its `type` and
+/// `trait` fields instead.
+fn definition_label(node: tree_sitter::Node, file_text_as_bytes: &[u8]) -> Option<String> {
+    if node.kind() == "impl_item" {
+        let type_name = node
+            .child_by_field_name("type")?
+            .utf8_text(file_text_as_bytes)
+            .ok()?;
+        return Some(match node.child_by_field_name("trait") {
+            Some(trait_node) => {
+                let trait_name = trait_node.utf8_text(file_text_as_bytes).ok()?;
+                format!("impl {trait_name} for {type_name}")
+            }
+            None => format!("impl {type_name}"),
+        });
+    }
+    let name = node.child_by_field_name("name")?.utf8_text(file_text_as_bytes).ok()?;
+    Some(format!("{} {name}", scope_label(node.kind())))
+}
+
+/// Returns the chain of named ancestor definitions enclosing the node at
+/// `start_byte..end_byte`, outermost first, e.g. `["mod tests", "fn
+/// it_works"]` for a statement inside `mod tests { fn it_works() { ... } }`.
+///
+/// Takes an already-parsed `tree` rather than parsing `file_text_as_bytes`
+/// itself, so that callers walking many matches in the same file (e.g.
+/// `--show-scope`) only pay for one parse per file.
+pub fn enclosing_scope(
+    tree: &Tree,
+    file_text_as_bytes: &[u8],
+    language: Language,
+    start_byte: usize,
+    end_byte: usize,
+) -> Vec<String> {
+    let definition_kinds = definition_node_kinds(language);
+
+    let mut names = Vec::new();
+    let mut current = tree
+        .root_node()
+        .descendant_for_byte_range(start_byte, end_byte)
+        .and_then(|node| node.parent());
+    while let Some(node) = current {
+        if definition_kinds.contains(&node.kind()) {
+            if let Some(label) = definition_label(node, file_text_as_bytes) {
+                names.push(label);
+            }
+        }
+        current = node.parent();
+    }
+    names.reverse();
+    names
 }
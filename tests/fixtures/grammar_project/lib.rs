@@ -0,0 +1 @@
+fn already_rust() {}
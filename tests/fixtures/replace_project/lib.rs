@@ -0,0 +1,3 @@
+fn helper() {}
+
+fn stop_it() {}
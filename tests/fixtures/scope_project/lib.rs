@@ -0,0 +1,23 @@
+pub fn add(left: usize, right: usize) -> usize {
+    left + right
+}
+
+pub struct Calculator;
+
+impl Calculator {
+    pub fn double(&self, value: usize) -> usize {
+        let doubled = value * 2;
+        doubled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let result = add(2, 2);
+        assert_eq!(result, 4);
+    }
+}
@@ -1,5 +1,5 @@
 #![allow(clippy::into_iter_on_ref, clippy::collapsible_if)]
-use std::{borrow::Cow, env, path::PathBuf, process::Command};
+use std::{borrow::Cow, env, fs, path::PathBuf, process::Command};
 
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
@@ -189,6 +189,21 @@ fn massage_error_output(output: &str) -> String {
     .join("\n")
 }
 
+fn build_fake_grammar(file_stem: &str) {
+    let fixture_dir = get_fixture_dir_path_from_name("grammar_project");
+    let source = fixture_dir.join("fake_grammar.c");
+    let library = fixture_dir.join(get_dynamic_library_name(file_stem));
+    let status = Command::new("cc")
+        .args(["-shared", "-fPIC", "-I"])
+        .arg(&fixture_dir)
+        .arg("-o")
+        .arg(&library)
+        .arg(&source)
+        .status()
+        .expect("Building fake grammar failed");
+    assert!(status.success(), "cc failed to build the fake grammar");
+}
+
 fn build_example(example_name: &str) {
     // CargoBuild::new().example(example_name).exec().unwrap();
     Command::new("cargo")
@@ -605,6 +620,151 @@ fn test_query_inline_and_query_file_path() {
     );
 }
 
+#[test]
+fn test_replace_dry_run() {
+    assert_non_match_output(
+        "replace_project",
+        r#"
+            $ tree-sitter-grep ./rename-helper.scm --replace "fn helper_renamed()" --dry-run
+            --- lib.rs (original)
+            +++ lib.rs (replaced)
+            @@ -1,3 +1,3 @@
+            -fn helper() {}
+            +fn helper_renamed()
+
+             fn stop_it() {}
+        "#,
+    );
+}
+
+#[test]
+fn test_replace_in_place() {
+    let fixture_dir = get_fixture_dir_path_from_name("replace_project");
+    let target_file = fixture_dir.join("lib.rs");
+    let original_contents = fs::read_to_string(&target_file).unwrap();
+
+    Command::cargo_bin("tree-sitter-grep")
+        .unwrap()
+        .args([
+            "./rename-helper.scm",
+            "--replace",
+            "fn helper_renamed()",
+            "--in-place",
+        ])
+        .current_dir(&fixture_dir)
+        .assert()
+        .success();
+
+    let rewritten_contents = fs::read_to_string(&target_file).unwrap();
+    fs::write(&target_file, &original_contents).unwrap();
+
+    assert_eq!(rewritten_contents, "fn helper_renamed()\n\nfn stop_it() {}\n");
+}
+
+#[test]
+fn test_replace_without_dry_run_or_in_place() {
+    assert_failure_output(
+        "replace_project",
+        r#"
+            $ tree-sitter-grep ./rename-helper.scm --replace "fn helper_renamed()"
+            error: the following required arguments were not provided:
+              <--dry-run|--in-place>
+
+            Usage: tree-sitter-grep --replace <REPLACE> <--dry-run|--in-place> <PATH_TO_QUERY_FILE>
+
+            For more information, try '--help'.
+        "#,
+    );
+}
+
+#[test]
+fn test_replace_with_dry_run_and_in_place() {
+    assert_failure_output(
+        "replace_project",
+        r#"
+            $ tree-sitter-grep ./rename-helper.scm --replace "fn helper_renamed()" --dry-run --in-place
+            error: the argument '--dry-run' cannot be used with '--in-place'
+
+            Usage: tree-sitter-grep --replace <REPLACE> <--dry-run|--in-place> <PATH_TO_QUERY_FILE>
+
+            For more information, try '--help'.
+        "#,
+    );
+}
+
+#[test]
+fn test_show_scope() {
+    assert_non_match_output(
+        "scope_project",
+        r#"
+            $ tree-sitter-grep ./let-statement.scm --show-scope
+            lib.rs:9: [impl Calculator > fn double] let doubled = value * 2;
+            lib.rs:20: [mod tests > fn it_works] let result = add(2, 2);
+        "#,
+    );
+}
+
+#[test]
+fn test_explicit_builtin_language() {
+    assert_non_match_output(
+        "scope_project",
+        r#"
+            $ tree-sitter-grep ./let-statement.scm --language rust
+            lib.rs:9:        let doubled = value * 2;
+            lib.rs:20:        let result = add(2, 2);
+        "#,
+    );
+}
+
+#[test]
+fn test_unknown_language_name() {
+    assert_failure_output(
+        "scope_project",
+        r#"
+            $ tree-sitter-grep ./let-statement.scm --language python
+            error: invalid value 'python' for '--language <LANGUAGE>'
+              [possible values: rust, typescript, javascript]
+
+            For more information, try '--help'.
+        "#,
+    );
+}
+
+#[test]
+fn test_grammar_flag_loads_a_dynamic_grammar() {
+    build_fake_grammar("tree-sitter-fakelang");
+
+    // `fake_grammar.c` is a real (if tiny) tree-sitter grammar, generated
+    // with `tree-sitter generate` rather than a null-pointer stub, so this
+    // exercises actually parsing `hello.fake` with the dlopen'd grammar:
+    // `(greeting)` isn't a node kind any built-in language has, so a
+    // match here can only have come from the loaded grammar.
+    assert_sorted_output(
+        "grammar_project",
+        r#"
+            $ tree-sitter-grep ./greeting.scm --grammar fake=./libtree-sitter-fakelang.so
+            hello.fake:1:hello world
+        "#,
+    );
+}
+
+#[test]
+fn test_grammar_flag_missing_entry_point() {
+    build_fake_grammar("tree-sitter-mismatch");
+    let library_name = get_dynamic_library_name("tree-sitter-mismatch");
+
+    Command::cargo_bin("tree-sitter-grep")
+        .unwrap()
+        .args([
+            "./function-item.scm",
+            &format!("--grammar=bad=./{library_name}"),
+        ])
+        .current_dir(get_fixture_dir_path_from_name("grammar_project"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("tree_sitter_mismatch"));
+}
+
 #[test]
 fn test_help_option() {
     assert_non_match_output(